@@ -1,4 +1,9 @@
-use std::{ffi::CStr, ptr, result, str::Utf8Error};
+use std::{
+    ffi::CStr,
+    io::{self, Read, Write},
+    iter, mem, ptr, result,
+    str::Utf8Error,
+};
 
 use bitflags::bitflags;
 use cstr_argument::CStrArgument;
@@ -214,6 +219,19 @@ impl Cipher {
         Ok(())
     }
 
+    #[inline]
+    pub fn sync(&mut self) -> Result<()> {
+        unsafe {
+            return_err!(ffi::gcry_cipher_ctl(
+                self.as_raw(),
+                ffi::GCRYCTL_CFB_SYNC as c_int,
+                ptr::null_mut(),
+                0
+            ));
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn authenticate(&mut self, bytes: &[u8]) -> Result<()> {
         unsafe {
@@ -250,6 +268,25 @@ impl Cipher {
         Ok(())
     }
 
+    #[inline]
+    pub fn set_ccm_lengths(
+        &mut self,
+        plaintext_len: u64,
+        aad_len: u64,
+        tag_len: u64,
+    ) -> Result<()> {
+        let lengths: [u64; 3] = [plaintext_len, aad_len, tag_len];
+        unsafe {
+            return_err!(ffi::gcry_cipher_ctl(
+                self.as_raw(),
+                ffi::GCRYCTL_SET_CCM_LENGTHS as c_int,
+                lengths.as_ptr() as *mut _,
+                mem::size_of_val(&lengths)
+            ));
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn encrypt(&mut self, input: &[u8], output: &mut [u8]) -> Result<()> {
         unsafe {
@@ -306,3 +343,397 @@ impl Cipher {
         Ok(())
     }
 }
+
+#[inline]
+fn is_block_mode(mode: Mode) -> bool {
+    matches!(mode, Mode::Ecb | Mode::Cbc)
+}
+
+fn set_iv_or_ctr(cipher: &mut Cipher, mode: Mode, iv: &[u8]) -> Result<()> {
+    if matches!(mode, Mode::Ctr) {
+        cipher.set_ctr(iv)
+    } else {
+        cipher.set_iv(iv)
+    }
+}
+
+fn pad(buf: &mut Vec<u8>, block_len: usize) {
+    let pad_len = block_len - buf.len() % block_len;
+    buf.extend(iter::repeat(pad_len as u8).take(pad_len));
+}
+
+fn unpad(buf: &mut Vec<u8>, block_len: usize) -> Result<()> {
+    let len = buf.len();
+    if len == 0 || len % block_len != 0 {
+        return_err!(ffi::gcry_error(ffi::GPG_ERR_BAD_DATA));
+    }
+
+    let pad_len = buf[len - 1];
+    let mut bad = (pad_len == 0) as u8 | (pad_len as usize > block_len) as u8;
+    for (i, &byte) in buf[len - block_len..].iter().enumerate() {
+        let is_pad_byte = (block_len - i) as u8 <= pad_len;
+        let mask = 0u8.wrapping_sub(is_pad_byte as u8);
+        bad |= mask & (byte ^ pad_len);
+    }
+    if bad != 0 {
+        return_err!(ffi::gcry_error(ffi::GPG_ERR_BAD_DATA));
+    }
+
+    buf.truncate(len - pad_len as usize);
+    Ok(())
+}
+
+/// Encrypts `data` in one shot, returning the ciphertext.
+///
+/// For the block modes (`Ecb`, `Cbc`) the input is padded with PKCS#7
+/// padding before encryption; the other modes are fed the data as-is.
+pub fn encrypt(
+    algo: Algorithm,
+    mode: Mode,
+    key: impl AsRef<[u8]>,
+    iv: Option<&[u8]>,
+    data: impl AsRef<[u8]>,
+) -> Result<Vec<u8>> {
+    let data = data.as_ref();
+    let mut cipher = Cipher::new(algo, mode)?;
+    cipher.set_key(key)?;
+    if let Some(iv) = iv {
+        set_iv_or_ctr(&mut cipher, mode, iv)?;
+    }
+
+    let mut buf = Vec::with_capacity(data.len() + algo.block_len());
+    buf.extend_from_slice(data);
+    if is_block_mode(mode) {
+        pad(&mut buf, algo.block_len());
+    }
+    cipher.encrypt_inplace(&mut buf)?;
+    Ok(buf)
+}
+
+/// Decrypts `data` in one shot, returning the plaintext.
+///
+/// For the block modes (`Ecb`, `Cbc`) the trailing PKCS#7 padding is
+/// validated and stripped from the result.
+pub fn decrypt(
+    algo: Algorithm,
+    mode: Mode,
+    key: impl AsRef<[u8]>,
+    iv: Option<&[u8]>,
+    data: impl AsRef<[u8]>,
+) -> Result<Vec<u8>> {
+    let data = data.as_ref();
+    let mut cipher = Cipher::new(algo, mode)?;
+    cipher.set_key(key)?;
+    if let Some(iv) = iv {
+        set_iv_or_ctr(&mut cipher, mode, iv)?;
+    }
+
+    let mut buf = data.to_vec();
+    cipher.decrypt_inplace(&mut buf)?;
+    if is_block_mode(mode) {
+        unpad(&mut buf, algo.block_len())?;
+    }
+    Ok(buf)
+}
+
+/// Seals `data` in one shot using an AEAD mode (`Gcm`, `Ocb`, `Eax`,
+/// `Ccm`, `Poly1305`), authenticating `aad` and writing the resulting
+/// tag into `tag`.
+pub fn encrypt_aead(
+    algo: Algorithm,
+    mode: Mode,
+    key: impl AsRef<[u8]>,
+    iv: Option<&[u8]>,
+    aad: &[u8],
+    data: impl AsRef<[u8]>,
+    tag: &mut [u8],
+) -> Result<Vec<u8>> {
+    let data = data.as_ref();
+    let mut cipher = Cipher::new(algo, mode)?;
+    cipher.set_key(key)?;
+    if let Some(iv) = iv {
+        set_iv_or_ctr(&mut cipher, mode, iv)?;
+    }
+    if matches!(mode, Mode::Ccm) {
+        cipher.set_ccm_lengths(data.len() as u64, aad.len() as u64, tag.len() as u64)?;
+    }
+    cipher.authenticate(aad)?;
+
+    let mut buf = vec![0u8; data.len()];
+    cipher.encrypt(data, &mut buf)?;
+    cipher.get_tag(tag)?;
+    Ok(buf)
+}
+
+/// Opens `data` in one shot using an AEAD mode, authenticating `aad`
+/// and verifying it against `tag` before returning the plaintext.
+pub fn decrypt_aead(
+    algo: Algorithm,
+    mode: Mode,
+    key: impl AsRef<[u8]>,
+    iv: Option<&[u8]>,
+    aad: &[u8],
+    data: impl AsRef<[u8]>,
+    tag: &[u8],
+) -> Result<Vec<u8>> {
+    let data = data.as_ref();
+    let mut cipher = Cipher::new(algo, mode)?;
+    cipher.set_key(key)?;
+    if let Some(iv) = iv {
+        set_iv_or_ctr(&mut cipher, mode, iv)?;
+    }
+    if matches!(mode, Mode::Ccm) {
+        cipher.set_ccm_lengths(data.len() as u64, aad.len() as u64, tag.len() as u64)?;
+    }
+    cipher.authenticate(aad)?;
+
+    let mut buf = vec![0u8; data.len()];
+    cipher.decrypt(data, &mut buf)?;
+    cipher.verify_tag(tag)?;
+    Ok(buf)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Encrypt,
+    Decrypt,
+}
+
+/// A stateful, buffered cipher that can be fed arbitrarily sized chunks
+/// of data via repeated calls to [`update`][Crypter::update], finishing
+/// with a single call to [`finalize`][Crypter::finalize].
+#[derive(Debug)]
+pub struct Crypter {
+    cipher: Cipher,
+    mode: Mode,
+    direction: Direction,
+    block_len: usize,
+    buffer: Vec<u8>,
+    pad: bool,
+}
+
+impl Crypter {
+    pub fn new(
+        algo: Algorithm,
+        mode: Mode,
+        direction: Direction,
+        key: impl AsRef<[u8]>,
+        iv: Option<&[u8]>,
+    ) -> Result<Crypter> {
+        let mut cipher = Cipher::new(algo, mode)?;
+        cipher.set_key(key)?;
+        if let Some(iv) = iv {
+            set_iv_or_ctr(&mut cipher, mode, iv)?;
+        }
+
+        Ok(Crypter {
+            cipher,
+            mode,
+            direction,
+            block_len: algo.block_len().max(1),
+            buffer: Vec::new(),
+            pad: is_block_mode(mode),
+        })
+    }
+
+    #[inline]
+    pub fn pad(&mut self, pad: bool) {
+        self.pad = pad;
+    }
+
+    fn process(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize> {
+        match self.direction {
+            Direction::Encrypt => self.cipher.encrypt(input, output)?,
+            Direction::Decrypt => self.cipher.decrypt(input, output)?,
+        }
+        Ok(input.len())
+    }
+
+    pub fn update(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize> {
+        if !is_block_mode(self.mode) {
+            return self.process(input, output);
+        }
+
+        self.buffer.extend_from_slice(input);
+        let mut take = self.buffer.len() - self.buffer.len() % self.block_len;
+        if matches!(self.direction, Direction::Decrypt) && take > 0 && take == self.buffer.len() {
+            // The last full block may carry the PKCS#7 padding, which only
+            // `finalize` can see and strip, so it must never be released
+            // early even if the buffer happens to be block-aligned here.
+            take -= self.block_len;
+        }
+        let rest = self.buffer.split_off(take);
+        let chunk = mem::replace(&mut self.buffer, rest);
+        self.process(&chunk, &mut output[..chunk.len()])
+    }
+
+    pub fn finalize(&mut self, output: &mut [u8]) -> Result<usize> {
+        if !is_block_mode(self.mode) {
+            return Ok(0);
+        }
+
+        let block_len = self.block_len;
+        let mut block = mem::take(&mut self.buffer);
+        match self.direction {
+            Direction::Encrypt => {
+                if self.pad {
+                    pad(&mut block, block_len);
+                }
+                self.process(&block, &mut output[..block.len()])
+            }
+            Direction::Decrypt => {
+                let len = block.len();
+                let mut buf = vec![0u8; len];
+                self.cipher.decrypt(&block, &mut buf)?;
+                if self.pad {
+                    unpad(&mut buf, block_len)?;
+                }
+                output[..buf.len()].copy_from_slice(&buf);
+                Ok(buf.len())
+            }
+        }
+    }
+}
+
+fn crypter_err(err: crate::error::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+/// Wraps a writer, encrypting (or decrypting) data as it passes through
+/// `write()`. The final, padded block is only emitted once [`finish`]
+/// is called, so `CipherWriter` must be explicitly finished rather than
+/// relying on `Drop`.
+///
+/// [`finish`]: CipherWriter::finish
+pub struct CipherWriter<W> {
+    inner: W,
+    crypter: Crypter,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> CipherWriter<W> {
+    #[inline]
+    pub fn new(inner: W, crypter: Crypter) -> CipherWriter<W> {
+        CipherWriter {
+            inner,
+            crypter,
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.buf.resize(self.crypter.block_len * 2, 0);
+        let n = self.crypter.finalize(&mut self.buf).map_err(crypter_err)?;
+        self.inner.write_all(&self.buf[..n])?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for CipherWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.resize(data.len() + self.crypter.block_len, 0);
+        let n = self
+            .crypter
+            .update(data, &mut self.buf)
+            .map_err(crypter_err)?;
+        self.inner.write_all(&self.buf[..n])?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wraps a reader, decrypting (or encrypting) data as it is pulled
+/// through `read()`.
+pub struct CipherReader<R> {
+    inner: R,
+    crypter: Crypter,
+    inbuf: Vec<u8>,
+    outbuf: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> CipherReader<R> {
+    #[inline]
+    pub fn new(inner: R, crypter: Crypter) -> CipherReader<R> {
+        CipherReader {
+            inner,
+            crypter,
+            inbuf: vec![0u8; 4096],
+            outbuf: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for CipherReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.outbuf.len() && !self.done {
+            let n = self.inner.read(&mut self.inbuf)?;
+            if n == 0 {
+                self.outbuf.resize(self.crypter.block_len * 2, 0);
+                let written = self.crypter.finalize(&mut self.outbuf).map_err(crypter_err)?;
+                self.outbuf.truncate(written);
+                self.done = true;
+            } else {
+                self.outbuf.resize(n + self.crypter.block_len, 0);
+                let written = self
+                    .crypter
+                    .update(&self.inbuf[..n], &mut self.outbuf)
+                    .map_err(crypter_err)?;
+                self.outbuf.truncate(written);
+            }
+            self.pos = 0;
+        }
+
+        let avail = &self.outbuf[self.pos..];
+        let len = avail.len().min(buf.len());
+        buf[..len].copy_from_slice(&avail[..len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"0123456789abcdef";
+    const IV: &[u8] = b"fedcba9876543210";
+
+    #[test]
+    fn encrypt_decrypt_roundtrip_cbc() {
+        let data = b"a message that does not land on a block boundary.";
+        let ciphertext = encrypt(Algorithm::Aes128, Mode::Cbc, KEY, Some(IV), data).unwrap();
+        let plaintext = decrypt(Algorithm::Aes128, Mode::Cbc, KEY, Some(IV), &ciphertext).unwrap();
+        assert_eq!(plaintext, data);
+    }
+
+    #[test]
+    fn encrypt_decrypt_roundtrip_ecb_block_aligned() {
+        let data = b"0123456789abcdef";
+        let ciphertext = encrypt(Algorithm::Aes128, Mode::Ecb, KEY, None, data).unwrap();
+        assert_eq!(ciphertext.len(), data.len() + Algorithm::Aes128.block_len());
+        let plaintext = decrypt(Algorithm::Aes128, Mode::Ecb, KEY, None, &ciphertext).unwrap();
+        assert_eq!(plaintext, data);
+    }
+
+    #[test]
+    fn crypter_decrypt_block_aligned_ciphertext_in_one_update() {
+        let data = b"exactly two 16-byte blocks here.";
+        let ciphertext = encrypt(Algorithm::Aes128, Mode::Cbc, KEY, Some(IV), data).unwrap();
+
+        let mut crypter =
+            Crypter::new(Algorithm::Aes128, Mode::Cbc, Direction::Decrypt, KEY, Some(IV)).unwrap();
+        let mut plaintext = vec![0u8; ciphertext.len()];
+        let mut written = crypter.update(&ciphertext, &mut plaintext).unwrap();
+        written += crypter.finalize(&mut plaintext[written..]).unwrap();
+        plaintext.truncate(written);
+
+        assert_eq!(plaintext, data);
+    }
+}